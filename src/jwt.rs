@@ -0,0 +1,344 @@
+//! Stateless bearer-token authentication, as a sibling to [`crate::session::SessionAuthProvider`].
+//!
+//! [`JwtAuthProvider`] implements the same [`AuthenticationProvider`] trait, so an app can
+//! wrap different routes in an [`AuthMiddleware`](crate::middleware::AuthMiddleware) backed
+//! by either provider: a session cookie for the browser app, a bearer token for API/SPA
+//! clients hitting the same user model.
+
+use std::{
+    future::{ready, Future},
+    marker::PhantomData,
+    pin::Pin,
+};
+
+use actix_web::{http::header, HttpRequest};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{AuthState, AuthToken, AuthenticationProvider, UnauthorizedError};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JwtClaims<U> {
+    exp: u64,
+    nbf: u64,
+    aud: String,
+    jti: String,
+    #[serde(default)]
+    mfa_pending: bool,
+    user: U,
+}
+
+/// Server-side revocation set for stateless bearer tokens.
+///
+/// Pushing a token's `jti` here on logout lets [`JwtAuthProvider::get_auth_token`] reject
+/// it ahead of its `exp`, despite the provider itself holding no session state.
+pub trait JwtRevocationStore {
+    fn revoke(&self, jti: &str);
+    fn is_revoked(&self, jti: &str) -> bool;
+}
+
+/// Provider for bearer-token authentication.
+///
+/// Reads the token from the `Authorization: Bearer` header, falling back to
+/// `cookie_name` for clients that cannot set custom headers. Verifies the signature with
+/// `decoding_key`/`algorithm` and the `exp`/`nbf`/`aud` claims, then reconstructs
+/// `AuthToken<U>` from the embedded user claim, mapping `mfa_pending` to
+/// [`AuthState::NeedsMfa`].
+#[derive(Clone)]
+pub struct JwtAuthProvider<U, R: JwtRevocationStore + Clone> {
+    decoding_key: DecodingKey,
+    algorithm: Algorithm,
+    audience: String,
+    cookie_name: String,
+    revocation: R,
+    _user: PhantomData<U>,
+}
+
+impl<U, R: JwtRevocationStore + Clone> JwtAuthProvider<U, R> {
+    pub fn new(
+        decoding_key: DecodingKey,
+        algorithm: Algorithm,
+        audience: impl Into<String>,
+        cookie_name: impl Into<String>,
+        revocation: R,
+    ) -> Self {
+        Self {
+            decoding_key,
+            algorithm,
+            audience: audience.into(),
+            cookie_name: cookie_name.into(),
+            revocation,
+            _user: PhantomData,
+        }
+    }
+
+    fn token_from_request(&self, req: &HttpRequest) -> Option<String> {
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_owned)
+            .or_else(|| req.cookie(&self.cookie_name).map(|c| c.value().to_owned()))
+    }
+
+    fn validation(&self) -> Validation {
+        let mut validation = Validation::new(self.algorithm);
+        validation.set_audience(&[&self.audience]);
+        validation
+    }
+}
+
+impl<U, R> AuthenticationProvider<U> for JwtAuthProvider<U, R>
+where
+    U: DeserializeOwned + Clone + 'static,
+    R: JwtRevocationStore + Clone + 'static,
+{
+    fn get_auth_token(
+        &self,
+        req: &HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<AuthToken<U>, UnauthorizedError>>>> {
+        let Some(token) = self.token_from_request(req) else {
+            return Box::pin(ready(Err(UnauthorizedError::default())));
+        };
+
+        let claims = match decode::<JwtClaims<U>>(&token, &self.decoding_key, &self.validation()) {
+            Ok(data) => data.claims,
+            Err(_) => return Box::pin(ready(Err(UnauthorizedError::default()))),
+        };
+
+        if self.revocation.is_revoked(&claims.jti) {
+            return Box::pin(ready(Err(UnauthorizedError::default())));
+        }
+
+        let state = if claims.mfa_pending {
+            AuthState::NeedsMfa
+        } else {
+            AuthState::Authenticated
+        };
+
+        Box::pin(ready(Ok(AuthToken::new(claims.user, state))))
+    }
+
+    fn invalidate(&self, req: HttpRequest) -> Pin<Box<dyn Future<Output = ()>>> {
+        let revocation = self.revocation.clone();
+
+        // Validated without checking `exp`: a token due to be revoked on logout is
+        // typically still unexpired, but revocation should also work for one that just did.
+        let mut validation = self.validation();
+        validation.validate_exp = false;
+
+        let jti = self
+            .token_from_request(&req)
+            .and_then(|token| decode::<JwtClaims<U>>(&token, &self.decoding_key, &validation).ok())
+            .map(|data| data.claims.jti);
+
+        Box::pin(async move {
+            if let Some(jti) = jti {
+                revocation.revoke(&jti);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use actix_web::test::TestRequest;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    use super::*;
+
+    const SECRET: &[u8] = b"test-signing-secret";
+    const AUDIENCE: &str = "authfix-tests";
+    const COOKIE_NAME: &str = "auth_token";
+
+    #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+    struct TestUser {
+        id: String,
+    }
+
+    #[derive(Clone, Default)]
+    struct InMemoryRevocationStore {
+        revoked: Arc<Mutex<HashSet<String>>>,
+    }
+
+    impl JwtRevocationStore for InMemoryRevocationStore {
+        fn revoke(&self, jti: &str) {
+            self.revoked.lock().unwrap().insert(jti.to_owned());
+        }
+
+        fn is_revoked(&self, jti: &str) -> bool {
+            self.revoked.lock().unwrap().contains(jti)
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn provider() -> JwtAuthProvider<TestUser, InMemoryRevocationStore> {
+        JwtAuthProvider::new(
+            DecodingKey::from_secret(SECRET),
+            Algorithm::HS256,
+            AUDIENCE,
+            COOKIE_NAME,
+            InMemoryRevocationStore::default(),
+        )
+    }
+
+    fn token(claims: &JwtClaims<TestUser>) -> String {
+        encode(&Header::new(Algorithm::HS256), claims, &EncodingKey::from_secret(SECRET))
+            .expect("encoding a test token should not fail")
+    }
+
+    fn valid_claims() -> JwtClaims<TestUser> {
+        JwtClaims {
+            exp: now() + 3600,
+            nbf: now() - 1,
+            aud: AUDIENCE.to_owned(),
+            jti: "jti-1".to_owned(),
+            mfa_pending: false,
+            user: TestUser { id: "user-1".to_owned() },
+        }
+    }
+
+    #[actix_web::test]
+    async fn rejects_an_expired_token() {
+        let provider = provider();
+        let mut claims = valid_claims();
+        claims.exp = now() - 60;
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token(&claims))))
+            .to_http_request();
+
+        assert!(AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_not_yet_valid_token() {
+        let provider = provider();
+        let mut claims = valid_claims();
+        claims.nbf = now() + 3600;
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token(&claims))))
+            .to_http_request();
+
+        assert!(AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_token_issued_for_a_different_audience() {
+        let provider = provider();
+        let mut claims = valid_claims();
+        claims.aud = "some-other-service".to_owned();
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token(&claims))))
+            .to_http_request();
+
+        assert!(AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn reads_the_token_from_the_authorization_header() {
+        let provider = provider();
+        let claims = valid_claims();
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token(&claims))))
+            .to_http_request();
+
+        assert!(AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn falls_back_to_the_cookie_when_no_authorization_header_is_present() {
+        let provider = provider();
+        let claims = valid_claims();
+
+        let req = TestRequest::default()
+            .cookie(actix_web::cookie::Cookie::new(COOKIE_NAME, token(&claims)))
+            .to_http_request();
+
+        assert!(AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn maps_mfa_pending_claim_to_needs_mfa_auth_state() {
+        let provider = provider();
+        let mut claims = valid_claims();
+        claims.mfa_pending = true;
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token(&claims))))
+            .to_http_request();
+
+        let auth_token = AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .expect("a structurally valid token should be accepted");
+
+        assert_eq!(auth_token.state, AuthState::NeedsMfa);
+    }
+
+    #[actix_web::test]
+    async fn maps_absent_mfa_pending_claim_to_authenticated_auth_state() {
+        let provider = provider();
+        let claims = valid_claims();
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token(&claims))))
+            .to_http_request();
+
+        let auth_token = AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .expect("a structurally valid token should be accepted");
+
+        assert_eq!(auth_token.state, AuthState::Authenticated);
+    }
+
+    #[actix_web::test]
+    async fn invalidate_revokes_the_jti_so_a_later_request_is_rejected() {
+        let provider = provider();
+        let claims = valid_claims();
+        let cookie_value = token(&claims);
+
+        let req = TestRequest::default()
+            .cookie(actix_web::cookie::Cookie::new(COOKIE_NAME, cookie_value.clone()))
+            .to_http_request();
+        assert!(AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req)
+            .await
+            .is_ok());
+
+        let invalidate_req = TestRequest::default()
+            .cookie(actix_web::cookie::Cookie::new(COOKIE_NAME, cookie_value.clone()))
+            .to_http_request();
+        AuthenticationProvider::<TestUser>::invalidate(&provider, invalidate_req).await;
+
+        let req_after = TestRequest::default()
+            .cookie(actix_web::cookie::Cookie::new(COOKIE_NAME, cookie_value))
+            .to_http_request();
+        assert!(
+            AuthenticationProvider::<TestUser>::get_auth_token(&provider, &req_after)
+                .await
+                .is_err(),
+            "a request bearing a revoked jti must be rejected even though it is unexpired"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Second factors that can satisfy a session stuck in [`AuthState::NeedsMfa`](crate::AuthState::NeedsMfa).
+//!
+//! A factor is identified by a stable [`MfaFactor::id`], which is what
+//! `SessionBasedLoginState::needs_mfa_with_id` refers to while a challenge is pending and
+//! what ends up in `factors_already_checked` once it is satisfied. This makes multiple
+//! factors (e.g. TOTP and WebAuthn) composable: `LoginSession::require_mfa_factors` takes
+//! the ordered list a login must satisfy, and `LoginSession::mfa_challenge_done` advances
+//! `needs_mfa_with_id` to the next outstanding factor as each one checks in, only flipping
+//! the session to `AuthState::Authenticated` once none remain.
+
+pub mod totp;
+pub mod webauthn;
+
+use std::{future::Future, pin::Pin};
+
+use crate::session::LoginSession;
+
+/// Identifies the user stored in the session for the purposes of per-user MFA state,
+/// e.g. looking up a TOTP secret or a registered WebAuthn credential.
+pub trait MfaSubject {
+    fn mfa_subject_id(&self) -> String;
+}
+
+/// A second factor verified after the primary login.
+///
+/// Implementors may read and write `session` to persist single-use state across the
+/// challenge (e.g. a TOTP replay counter or a WebAuthn challenge), so that verifying the
+/// same response twice fails.
+pub trait MfaFactor {
+    /// Stable identifier stored in `needs_mfa_with_id` / `factors_already_checked`.
+    fn id(&self) -> &str;
+
+    /// Verifies `response` for `user_id`, returning `true` iff the factor is satisfied.
+    fn verify(
+        &self,
+        user_id: &str,
+        response: &str,
+        session: &LoginSession,
+    ) -> Pin<Box<dyn Future<Output = bool>>>;
+}
@@ -0,0 +1,277 @@
+use std::{
+    future::{ready, Future},
+    pin::Pin,
+    time::{Duration, SystemTime},
+};
+
+use webauthn_rs::{
+    prelude::{
+        CreationChallengeResponse, Passkey, PasskeyAuthentication, PasskeyRegistration,
+        PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse, Uuid,
+        WebauthnError,
+    },
+    Webauthn,
+};
+
+use crate::session::LoginSession;
+
+use super::MfaFactor;
+
+/// How long a generated challenge remains valid, binding it to the session that requested it.
+const CHALLENGE_TTL: Duration = Duration::from_secs(60);
+
+/// Persists and looks up the WebAuthn credentials registered for a user.
+pub trait WebauthnCredentialStore {
+    fn credentials_for(&self, user_id: &str) -> Vec<Passkey>;
+    fn save_credential(&self, user_id: &str, credential: Passkey);
+}
+
+/// Maps the application's user to the [`Uuid`] WebAuthn's `user.id` requires.
+///
+/// Kept separate from [`MfaSubject::mfa_subject_id`](super::MfaSubject): that one is an
+/// arbitrary per-user lookup key (e.g. a username or a numeric id) good enough for TOTP,
+/// while WebAuthn specifically needs a stable 128-bit user handle.
+pub trait WebauthnUserId {
+    fn webauthn_user_id(&self) -> Uuid;
+}
+
+/// WebAuthn/passkey second factor.
+///
+/// Wraps a configured [`Webauthn`] relying-party context and a [`WebauthnCredentialStore`]
+/// for per-user credential lookup and persistence.
+#[derive(Clone)]
+pub struct WebauthnFactor<S: WebauthnCredentialStore + Clone> {
+    id: String,
+    webauthn: Webauthn,
+    credentials: S,
+}
+
+impl<S: WebauthnCredentialStore + Clone> WebauthnFactor<S> {
+    pub fn new(id: impl Into<String>, webauthn: Webauthn, credentials: S) -> Self {
+        Self {
+            id: id.into(),
+            webauthn,
+            credentials,
+        }
+    }
+
+    /// Begins registering a new passkey for `user_id`, stashing the in-progress
+    /// [`PasskeyRegistration`] state on `session` bound to a short-lived challenge.
+    pub fn register_start(
+        &self,
+        user_id: Uuid,
+        user_name: &str,
+        session: &LoginSession,
+    ) -> Result<CreationChallengeResponse, WebauthnError> {
+        let exclude: Vec<_> = self
+            .credentials
+            .credentials_for(&user_id.to_string())
+            .iter()
+            .map(|passkey| passkey.cred_id().clone())
+            .collect();
+
+        let (challenge, state) =
+            self.webauthn
+                .start_passkey_registration(user_id, user_name, user_name, Some(exclude))?;
+
+        session
+            .set_webauthn_challenge(&state, SystemTime::now() + CHALLENGE_TTL)
+            .map_err(|_| WebauthnError::InvalidSessionState)?;
+
+        Ok(challenge)
+    }
+
+    /// Verifies the registration response against the challenge stashed by
+    /// [`WebauthnFactor::register_start`] and persists the resulting credential.
+    pub fn register_finish(
+        &self,
+        user_id: &str,
+        response: &RegisterPublicKeyCredential,
+        session: &LoginSession,
+    ) -> Result<(), WebauthnError> {
+        let state: PasskeyRegistration = session
+            .webauthn_challenge()
+            .ok_or(WebauthnError::ChallengeNotFound)?;
+        session.clear_webauthn_challenge();
+
+        let passkey = self.webauthn.finish_passkey_registration(response, &state)?;
+        self.credentials.save_credential(user_id, passkey);
+
+        Ok(())
+    }
+
+    /// Begins an authentication ceremony for `user_id`: generates a CSPRNG challenge via
+    /// [`Webauthn::start_passkey_authentication`] and stashes the resulting
+    /// [`PasskeyAuthentication`] state on `session`.
+    ///
+    /// Only proceeds if `self.id` is already `session`'s `needs_mfa_with_id` — i.e. a
+    /// factor chain set up elsewhere (by `LoginSession::require_mfa_factors` at primary
+    /// login) already expects WebAuthn next. This deliberately does *not* itself call
+    /// `needs_mfa`/`require_mfa_factors`: doing so would let starting a ceremony replace
+    /// whatever chain (e.g. `["totp", "webauthn"]`) the app configured, skipping earlier
+    /// required factors.
+    pub fn auth_start(
+        &self,
+        user_id: &str,
+        session: &LoginSession,
+    ) -> Result<RequestChallengeResponse, WebauthnError> {
+        if session.needs_mfa_with_id().as_deref() != Some(self.id.as_str()) {
+            return Err(WebauthnError::InvalidSessionState);
+        }
+
+        let credentials = self.credentials.credentials_for(user_id);
+        let (challenge, state) = self.webauthn.start_passkey_authentication(&credentials)?;
+
+        session
+            .set_webauthn_challenge(&state, SystemTime::now() + CHALLENGE_TTL)
+            .map_err(|_| WebauthnError::InvalidSessionState)?;
+
+        Ok(challenge)
+    }
+}
+
+impl<S: WebauthnCredentialStore + Clone + 'static> MfaFactor for WebauthnFactor<S> {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Verifies a signed assertion (`response`, the JSON-serialized
+    /// [`PublicKeyCredential`]) against the challenge stashed by
+    /// [`WebauthnFactor::auth_start`]. The challenge is single-use: it is removed from the
+    /// session regardless of outcome, and a session that has passed
+    /// [`LoginSession::no_longer_valid`] or has no matching challenge is rejected outright.
+    fn verify(
+        &self,
+        _user_id: &str,
+        response: &str,
+        session: &LoginSession,
+    ) -> Pin<Box<dyn Future<Output = bool>>> {
+        if session.no_longer_valid() {
+            return Box::pin(ready(false));
+        }
+
+        let assertion: Result<PublicKeyCredential, _> = serde_json::from_str(response);
+        let state = session.webauthn_challenge::<PasskeyAuthentication>();
+        session.clear_webauthn_challenge();
+
+        let verified = match (assertion, state) {
+            (Ok(assertion), Some(state)) => self
+                .webauthn
+                .finish_passkey_authentication(&assertion, &state)
+                .is_ok(),
+            _ => false,
+        };
+
+        Box::pin(ready(verified))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use actix_session::Session;
+    use actix_web::test;
+    use webauthn_rs::prelude::{Url, WebauthnBuilder};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct NoCredentials {
+        saved: Mutex<Vec<(String, Passkey)>>,
+    }
+
+    impl WebauthnCredentialStore for NoCredentials {
+        fn credentials_for(&self, _user_id: &str) -> Vec<Passkey> {
+            Vec::new()
+        }
+
+        fn save_credential(&self, user_id: &str, credential: Passkey) {
+            self.saved.lock().unwrap().push((user_id.to_owned(), credential));
+        }
+    }
+
+    fn test_login_session() -> LoginSession {
+        let req = test::TestRequest::default().to_http_request();
+        Session::set_session(&mut req.extensions_mut(), HashMap::new());
+        LoginSession::new(req.get_session())
+    }
+
+    fn test_webauthn() -> Webauthn {
+        let rp_origin = Url::parse("https://localhost:8080").expect("valid URL");
+        WebauthnBuilder::new("localhost", &rp_origin)
+            .expect("valid relying party config")
+            .build()
+            .expect("valid Webauthn config")
+    }
+
+    fn test_factor(id: &str) -> WebauthnFactor<NoCredentials> {
+        WebauthnFactor::new(id, test_webauthn(), NoCredentials::default())
+    }
+
+    #[test]
+    fn webauthn_challenge_expires_after_its_ttl() {
+        let session = test_login_session();
+
+        session
+            .set_webauthn_challenge(&"challenge-state", SystemTime::now() - Duration::from_secs(1))
+            .expect("session insert should not fail in tests");
+
+        assert_eq!(session.webauthn_challenge::<String>(), None);
+    }
+
+    #[test]
+    fn webauthn_challenge_is_single_use() {
+        let session = test_login_session();
+
+        session
+            .set_webauthn_challenge(&"challenge-state", SystemTime::now() + CHALLENGE_TTL)
+            .expect("session insert should not fail in tests");
+
+        assert_eq!(
+            session.webauthn_challenge::<String>(),
+            Some("challenge-state".to_owned())
+        );
+        session.clear_webauthn_challenge();
+
+        assert_eq!(
+            session.webauthn_challenge::<String>(),
+            None,
+            "a challenge must not be readable a second time once consumed"
+        );
+    }
+
+    #[actix_web::test]
+    async fn verify_rejects_once_the_session_is_no_longer_valid() {
+        let session = test_login_session();
+        let factor = test_factor("webauthn");
+
+        // No `valid_until` has been set, so `no_longer_valid()` defaults to `true`: the
+        // factor must refuse to even attempt parsing `response`.
+        assert!(!factor.verify("user-1", "not a valid assertion", &session).await);
+    }
+
+    #[test]
+    fn auth_start_refuses_to_run_ahead_of_the_expected_factor_chain() {
+        let session = test_login_session();
+        session
+            .require_mfa_factors(&["totp".to_owned(), "webauthn".to_owned()])
+            .expect("session insert should not fail in tests");
+
+        let webauthn_factor = test_factor("webauthn");
+
+        // TOTP is still pending: starting the WebAuthn ceremony must not be allowed to
+        // jump ahead and satisfy the session on its own.
+        let blocked = webauthn_factor.auth_start("user-1", &session);
+        assert!(matches!(blocked, Err(WebauthnError::InvalidSessionState)));
+
+        // Once TOTP has checked in, WebAuthn becomes the expected factor and is allowed
+        // to proceed (any further error here would come from the ceremony itself, not
+        // from the chain guard).
+        session.mfa_challenge_done("totp");
+        assert_eq!(session.needs_mfa_with_id(), Some("webauthn".to_owned()));
+
+        let allowed = webauthn_factor.auth_start("user-1", &session);
+        assert!(!matches!(allowed, Err(WebauthnError::InvalidSessionState)));
+    }
+}
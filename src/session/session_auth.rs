@@ -1,11 +1,11 @@
 use std::{
     future::{ready, Future, Ready},
     pin::Pin,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
 use actix_session::{
-    storage::CookieSessionStore, Session, SessionExt, SessionInsertError, SessionMiddleware,
+    storage::SessionStore, Session, SessionExt, SessionInsertError, SessionMiddleware,
 };
 use actix_web::{
     body::MessageBody,
@@ -24,16 +24,54 @@ use crate::{
 use super::handlers::{login_config, SessionLoginHandler};
 
 const SESSION_KEY_USER: &str = "user";
-const SESSION_KEY_NEED_MFA: &str = "needs_mfa";
+const SESSION_KEY_MFA_STATE: &str = "mfa_login_state";
 const SESSION_KEY_LOGIN_VALID_UNTIL: &str = "login_valid_until";
+const SESSION_KEY_TOTP_LAST_COUNTER: &str = "totp_last_counter";
+const SESSION_KEY_WEBAUTHN_STATE: &str = "webauthn_state";
+const SESSION_KEY_WEBAUTHN_VALID_UNTIL: &str = "webauthn_valid_until";
+const SESSION_KEY_LOGIN_TIME: &str = "login_time";
+const SESSION_KEY_LAST_SEEN: &str = "last_seen";
+
+/// Default sliding inactivity window: how long a session may go without a request
+/// before it is considered abandoned.
+const DEFAULT_INACTIVITY_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// Default absolute session lifetime, fixed at login and never extended.
+const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(6 * 60 * 60);
 
 /// Provider for session based authentication.
 ///
 /// Uses [Actix-Session](https://docs.rs/actix-session/latest/actix_session/), so it must be set as middleware.
+/// Works identically regardless of the configured [`SessionStore`](actix_session::storage::SessionStore)
+/// backend: with a server-side store (e.g. Redis, via [`redis_session_store`]), `invalidate`
+/// deletes the stored session entry instead of merely purging the client cookie.
+///
+/// Enforces two independent expiry bounds, like a real session manager: a sliding
+/// `inactivity_timeout` refreshed on every authenticated request, and an absolute
+/// `max_lifetime` fixed at login that no amount of activity extends. A session that
+/// exceeds either is rejected and purged.
 /// # Examples
 /// See crate example.
 #[derive(Clone)]
-pub struct SessionAuthProvider;
+pub struct SessionAuthProvider {
+    inactivity_timeout: Duration,
+    max_lifetime: Duration,
+}
+
+impl SessionAuthProvider {
+    pub fn new(inactivity_timeout: Duration, max_lifetime: Duration) -> Self {
+        Self {
+            inactivity_timeout,
+            max_lifetime,
+        }
+    }
+}
+
+impl Default for SessionAuthProvider {
+    /// 30 minutes of inactivity, 6 hours absolute lifetime.
+    fn default() -> Self {
+        Self::new(DEFAULT_INACTIVITY_TIMEOUT, DEFAULT_MAX_LIFETIME)
+    }
+}
 
 impl<U> AuthenticationProvider<U> for SessionAuthProvider
 where
@@ -44,6 +82,7 @@ where
         req: &actix_web::HttpRequest,
     ) -> Pin<Box<dyn Future<Output = Result<AuthToken<U>, UnauthorizedError>>>> {
         let s = req.get_session().clone();
+        let login_session = LoginSession::new(s.clone());
 
         // ToDo: refactor: remove the matches here
         let user = match s.get::<U>(SESSION_KEY_USER) {
@@ -51,13 +90,34 @@ where
             _ => return Box::pin(ready(Err(UnauthorizedError::default()))),
         };
 
-        let state = match s.get::<String>(SESSION_KEY_NEED_MFA) {
-            Ok(Some(_mfa_id)) => AuthState::NeedsMfa,
-            Ok(None) => AuthState::Authenticated,
-            Err(_) => {
-                error!("Cannot read `need_mfa' value from session");
-                return Box::pin(ready(Err(UnauthorizedError::default())));
+        let now = SystemTime::now();
+        let expired = match (login_session.login_time(), login_session.last_seen()) {
+            (Some(login_time), Some(last_seen)) => {
+                now.duration_since(last_seen)
+                    .map(|idle| idle > self.inactivity_timeout)
+                    .unwrap_or(false)
+                    || now
+                        .duration_since(login_time)
+                        .map(|age| age > self.max_lifetime)
+                        .unwrap_or(false)
             }
+            _ => true,
+        };
+
+        if expired {
+            s.purge();
+            return Box::pin(ready(Err(UnauthorizedError::default())));
+        }
+
+        if login_session.touch(now).is_err() {
+            error!("Cannot refresh `last_seen` in session");
+            return Box::pin(ready(Err(UnauthorizedError::default())));
+        }
+
+        let state = if login_session.needs_mfa_with_id().is_some() {
+            AuthState::NeedsMfa
+        } else {
+            AuthState::Authenticated
         };
 
         Box::pin(ready(Ok(AuthToken::new(user, state))))
@@ -76,6 +136,7 @@ struct SessionBasedLoginState {
     authenticated: bool,                  // if true, is fully authenticated for app
     factors_already_checked: Vec<String>, // IDs of checked factors
     needs_mfa_with_id: Option<String>,    // ID of next factor
+    remaining_factors: Vec<String>,       // IDs of factors not yet offered
     mfa_code: Option<String>,
     valid_unti: SystemTime, // after this timestamp LoginState is discarded
 }
@@ -106,16 +167,102 @@ impl LoginSession {
         Self { session }
     }
 
-    pub fn mfa_challenge_done(&self) {
-        self.session.remove(SESSION_KEY_NEED_MFA);
+    fn mfa_state(&self) -> Option<SessionBasedLoginState> {
+        self.session.get::<SessionBasedLoginState>(SESSION_KEY_MFA_STATE).ok().flatten()
+    }
+
+    /// Starts an MFA chain: `factor_ids` is the ordered list of factors still required,
+    /// the first becomes `needs_mfa_with_id` and the rest are offered in turn as each
+    /// earlier factor completes (see [`LoginSession::mfa_challenge_done`]).
+    pub fn require_mfa_factors(&self, factor_ids: &[String]) -> Result<(), SessionInsertError> {
+        let mut remaining_factors = factor_ids.to_vec();
+
+        if remaining_factors.is_empty() {
+            self.session.remove(SESSION_KEY_MFA_STATE);
+            return Ok(());
+        }
+
+        let needs_mfa_with_id = Some(remaining_factors.remove(0));
+
+        self.session.insert(
+            SESSION_KEY_MFA_STATE,
+            SessionBasedLoginState {
+                authenticated: false,
+                factors_already_checked: Vec::new(),
+                needs_mfa_with_id,
+                remaining_factors,
+                mfa_code: None,
+                valid_unti: SystemTime::now(),
+            },
+        )
     }
 
+    /// Convenience for a single required factor; equivalent to
+    /// `require_mfa_factors(&[mfa_id.to_owned()])`.
     pub fn needs_mfa(&self, mfa_id: &str) -> Result<(), SessionInsertError> {
-        self.session.insert(SESSION_KEY_NEED_MFA, mfa_id)
+        self.require_mfa_factors(&[mfa_id.to_owned()])
+    }
+
+    /// Marks `completed_factor_id` as satisfied and advances to the next pending factor,
+    /// if any. Once every required factor has checked in, the MFA state is cleared
+    /// entirely, so the session reads back as [`AuthState::Authenticated`]. A factor id
+    /// that doesn't match the one currently pending is ignored, so a completed earlier
+    /// factor can't be replayed to skip a later one.
+    pub fn mfa_challenge_done(&self, completed_factor_id: &str) {
+        let Some(mut state) = self.mfa_state() else {
+            return;
+        };
+
+        if state.needs_mfa_with_id.as_deref() != Some(completed_factor_id) {
+            return;
+        }
+
+        state.factors_already_checked.push(completed_factor_id.to_owned());
+
+        if state.remaining_factors.is_empty() {
+            self.session.remove(SESSION_KEY_MFA_STATE);
+            return;
+        }
+
+        state.needs_mfa_with_id = Some(state.remaining_factors.remove(0));
+        let _ = self.session.insert(SESSION_KEY_MFA_STATE, state);
     }
 
+    /// Sets the logged-in user and starts the session's `login_time`/`last_seen` timers
+    /// (see [`LoginSession::start_session_timers`]), so the very next request is already
+    /// subject to [`SessionAuthProvider`]'s inactivity/lifetime bounds.
     pub fn set_user<U: Serialize>(&self, user: U) -> Result<(), SessionInsertError> {
-        self.session.insert(SESSION_KEY_USER, user)
+        self.session.insert(SESSION_KEY_USER, user)?;
+        self.start_session_timers(SystemTime::now())
+    }
+
+    /// Reads back the user stored by [`LoginSession::set_user`].
+    pub(crate) fn user<U: DeserializeOwned>(&self) -> Option<U> {
+        self.session.get::<U>(SESSION_KEY_USER).ok().flatten()
+    }
+
+    /// ID of the factor the session is currently waiting on, if any.
+    pub(crate) fn needs_mfa_with_id(&self) -> Option<String> {
+        self.mfa_state().and_then(|state| state.needs_mfa_with_id)
+    }
+
+    /// Records `login_time` and `last_seen` as `now`. Call once on successful login;
+    /// [`SessionAuthProvider::get_auth_token`] refreshes `last_seen` on every later request.
+    pub fn start_session_timers(&self, now: SystemTime) -> Result<(), SessionInsertError> {
+        self.session.insert(SESSION_KEY_LOGIN_TIME, now)?;
+        self.session.insert(SESSION_KEY_LAST_SEEN, now)
+    }
+
+    pub(crate) fn login_time(&self) -> Option<SystemTime> {
+        self.session.get::<SystemTime>(SESSION_KEY_LOGIN_TIME).ok().flatten()
+    }
+
+    pub(crate) fn last_seen(&self) -> Option<SystemTime> {
+        self.session.get::<SystemTime>(SESSION_KEY_LAST_SEEN).ok().flatten()
+    }
+
+    pub(crate) fn touch(&self, now: SystemTime) -> Result<(), SessionInsertError> {
+        self.session.insert(SESSION_KEY_LAST_SEEN, now)
     }
 
     pub fn valid_until(&self, valid_until: SystemTime) -> Result<(), SessionInsertError> {
@@ -133,6 +280,58 @@ impl LoginSession {
         }
     }
 
+    /// Counter accepted by the last successful TOTP verification, if any.
+    ///
+    /// Compared against the counter a new code matches so the same code cannot be
+    /// replayed within its validity window.
+    pub(crate) fn totp_last_accepted_counter(&self) -> Option<u64> {
+        self.session
+            .get::<u64>(SESSION_KEY_TOTP_LAST_COUNTER)
+            .ok()
+            .flatten()
+    }
+
+    pub(crate) fn set_totp_last_accepted_counter(
+        &self,
+        counter: u64,
+    ) -> Result<(), SessionInsertError> {
+        self.session.insert(SESSION_KEY_TOTP_LAST_COUNTER, counter)
+    }
+
+    /// Stashes an in-progress WebAuthn ceremony state, single-use and bound to this
+    /// session: it is readable only until `valid_until` and is meant to be cleared by
+    /// [`LoginSession::clear_webauthn_challenge`] as soon as it is consumed.
+    pub(crate) fn set_webauthn_challenge<T: Serialize>(
+        &self,
+        state: &T,
+        valid_until: SystemTime,
+    ) -> Result<(), SessionInsertError> {
+        self.session.insert(SESSION_KEY_WEBAUTHN_STATE, state)?;
+        self.session
+            .insert(SESSION_KEY_WEBAUTHN_VALID_UNTIL, valid_until)
+    }
+
+    /// Reads back the state stashed by [`LoginSession::set_webauthn_challenge`], unless
+    /// its `valid_until` has already passed.
+    pub(crate) fn webauthn_challenge<T: DeserializeOwned>(&self) -> Option<T> {
+        let valid_until = self
+            .session
+            .get::<SystemTime>(SESSION_KEY_WEBAUTHN_VALID_UNTIL)
+            .ok()
+            .flatten()?;
+
+        if SystemTime::now() > valid_until {
+            return None;
+        }
+
+        self.session.get::<T>(SESSION_KEY_WEBAUTHN_STATE).ok().flatten()
+    }
+
+    pub(crate) fn clear_webauthn_challenge(&self) {
+        self.session.remove(SESSION_KEY_WEBAUTHN_STATE);
+        self.session.remove(SESSION_KEY_WEBAUTHN_VALID_UNTIL);
+    }
+
     pub fn reset(&self) {
         self.session.renew();
         self.session.clear();
@@ -154,9 +353,17 @@ impl FromRequest for LoginSession {
 }
 
 /// Factory function to generate an actix_web::App instance with session login
-pub fn session_login_factory<U: Serialize + DeserializeOwned + Clone + 'static>(
+///
+/// The session store backing the login state is pluggable: pass
+/// [`CookieSessionStore`](actix_session::storage::CookieSessionStore) to keep the whole
+/// session in the client cookie (the previous, hard-coded behaviour), or a server-side
+/// store such as [`RedisSessionStore`](actix_session::storage::RedisSessionStore) (see
+/// [`redis_session_store`] behind the `redis` feature) to keep only a session id in the
+/// cookie and make `SessionAuthProvider::invalidate` actually revoke the session server-side.
+pub fn session_login_factory<U: Serialize + DeserializeOwned + Clone + 'static, S: SessionStore + 'static>(
     login_handler: SessionLoginHandler<impl LoadUserService<User = U> + 'static, U>,
     auth_middleware: AuthMiddleware<impl AuthenticationProvider<U> + Clone + 'static, U>,
+    store: S,
     session_key: Key,
 ) -> App<
     impl ServiceFactory<
@@ -170,9 +377,246 @@ pub fn session_login_factory<U: Serialize + DeserializeOwned + Clone + 'static>(
     App::new()
         .configure(login_config(login_handler))
         .wrap(auth_middleware)
-        .wrap(create_actix_session_middleware(session_key.clone()))
+        .wrap(create_actix_session_middleware(store, session_key.clone()))
+}
+
+fn create_actix_session_middleware<S: SessionStore + 'static>(
+    store: S,
+    key: Key,
+) -> SessionMiddleware<S> {
+    SessionMiddleware::new(store, key)
 }
 
-fn create_actix_session_middleware(key: Key) -> SessionMiddleware<CookieSessionStore> {
-    SessionMiddleware::new(CookieSessionStore::default(), key)
+/// Connects to Redis and builds a [`SessionMiddleware`] backed by it.
+///
+/// Use this together with [`session_login_factory`] to store the login/MFA state
+/// server-side instead of in the client cookie, so
+/// `SessionAuthProvider::invalidate` can revoke a session by deleting its Redis entry
+/// rather than just purging the cookie.
+#[cfg(feature = "redis")]
+pub async fn redis_session_store(
+    redis_connection_string: impl Into<String>,
+) -> Result<actix_session::storage::RedisSessionStore, anyhow::Error> {
+    actix_session::storage::RedisSessionStore::new(redis_connection_string)
+        .await
+        .map_err(anyhow::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+    use actix_web::{cookie::time, test, web, App, HttpResponse};
+    use async_trait::async_trait;
+
+    use super::*;
+
+    #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+    struct TestUser {
+        id: String,
+    }
+
+    /// In-memory stand-in for a server-side store (e.g. Redis): the session payload lives
+    /// here rather than in the client cookie, so tests can inspect it directly.
+    #[derive(Clone, Default)]
+    struct InMemoryStore {
+        sessions: Arc<Mutex<HashMap<String, HashMap<String, String>>>>,
+    }
+
+    fn fresh_session_key() -> String {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        format!("test-session-{}", COUNTER.fetch_add(1, Ordering::SeqCst))
+    }
+
+    #[async_trait(?Send)]
+    impl SessionStore for InMemoryStore {
+        async fn load(
+            &self,
+            session_key: &SessionKey,
+        ) -> Result<Option<HashMap<String, String>>, LoadError> {
+            Ok(self.sessions.lock().unwrap().get(session_key.as_ref()).cloned())
+        }
+
+        async fn save(
+            &self,
+            session_state: HashMap<String, String>,
+            _ttl: &time::Duration,
+        ) -> Result<SessionKey, SaveError> {
+            let key = fresh_session_key();
+            self.sessions.lock().unwrap().insert(key.clone(), session_state);
+            key.try_into()
+                .map_err(|_| SaveError::Serialization(anyhow::anyhow!("invalid session key")))
+        }
+
+        async fn update(
+            &self,
+            session_key: SessionKey,
+            session_state: HashMap<String, String>,
+            _ttl: &time::Duration,
+        ) -> Result<SessionKey, UpdateError> {
+            self.sessions
+                .lock()
+                .unwrap()
+                .insert(session_key.as_ref().to_owned(), session_state);
+            Ok(session_key)
+        }
+
+        async fn update_ttl(&self, _session_key: &SessionKey, _ttl: &time::Duration) -> Result<(), anyhow::Error> {
+            Ok(())
+        }
+
+        async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+            self.sessions.lock().unwrap().remove(session_key.as_ref());
+            Ok(())
+        }
+    }
+
+    async fn login(login_session: LoginSession) -> HttpResponse {
+        login_session
+            .set_user(TestUser { id: "user-1".to_owned() })
+            .expect("session insert should not fail in tests");
+        HttpResponse::Ok().finish()
+    }
+
+    async fn whoami(provider: web::Data<SessionAuthProvider>, req: HttpRequest) -> HttpResponse {
+        match AuthenticationProvider::<TestUser>::get_auth_token(provider.get_ref(), &req).await {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(_) => HttpResponse::Unauthorized().finish(),
+        }
+    }
+
+    async fn logout(provider: web::Data<SessionAuthProvider>, req: HttpRequest) -> HttpResponse {
+        AuthenticationProvider::<TestUser>::invalidate(provider.get_ref(), req).await;
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn session_is_shared_across_requests_and_invalidate_clears_the_store() {
+        let store = InMemoryStore::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(SessionAuthProvider::default()))
+                .wrap(create_actix_session_middleware(store.clone(), Key::generate()))
+                .route("/login", web::post().to(login))
+                .route("/whoami", web::get().to(whoami))
+                .route("/logout", web::post().to(logout)),
+        )
+        .await;
+
+        let login_resp =
+            test::call_service(&app, test::TestRequest::post().uri("/login").to_request()).await;
+        let cookie = login_resp.response().cookies().next().unwrap().into_owned();
+        assert_eq!(store.sessions.lock().unwrap().len(), 1, "login should persist to the store");
+
+        // A second request, sharing only the store (not an in-process cache), can still
+        // read the user back.
+        let whoami_resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/whoami")
+                .cookie(cookie.clone())
+                .to_request(),
+        )
+        .await;
+        assert!(whoami_resp.status().is_success());
+
+        test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/logout")
+                .cookie(cookie)
+                .to_request(),
+        )
+        .await;
+
+        assert!(
+            store.sessions.lock().unwrap().is_empty(),
+            "invalidate should delete the server-side entry, not just purge the cookie"
+        );
+    }
+
+    fn app_with_provider(
+        provider: SessionAuthProvider,
+    ) -> App<
+        impl actix_web::dev::ServiceFactory<
+            actix_web::dev::ServiceRequest,
+            Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+            Config = (),
+            InitError = (),
+            Error = Error,
+        >,
+    > {
+        App::new()
+            .app_data(web::Data::new(provider))
+            .wrap(create_actix_session_middleware(
+                actix_session::storage::CookieSessionStore::default(),
+                Key::generate(),
+            ))
+            .route("/login", web::post().to(login))
+            .route("/whoami", web::get().to(whoami))
+    }
+
+    #[actix_web::test]
+    async fn activity_keeps_session_alive_past_the_inactivity_window() {
+        let provider = SessionAuthProvider::new(Duration::from_millis(60), Duration::from_secs(3600));
+        let app = test::init_service(app_with_provider(provider)).await;
+
+        let login_resp =
+            test::call_service(&app, test::TestRequest::post().uri("/login").to_request()).await;
+        let mut cookie = login_resp.response().cookies().next().unwrap().into_owned();
+
+        for _ in 0..3 {
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(30)).await;
+
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri("/whoami")
+                    .cookie(cookie.clone())
+                    .to_request(),
+            )
+            .await;
+            assert!(
+                resp.status().is_success(),
+                "a request within the inactivity window should refresh last_seen and keep the session alive"
+            );
+
+            if let Some(refreshed) = resp.response().cookies().next() {
+                cookie = refreshed.into_owned();
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn absolute_lifetime_forces_logout_despite_continuous_activity() {
+        let provider = SessionAuthProvider::new(Duration::from_secs(3600), Duration::from_millis(60));
+        let app = test::init_service(app_with_provider(provider)).await;
+
+        let login_resp =
+            test::call_service(&app, test::TestRequest::post().uri("/login").to_request()).await;
+        let cookie = login_resp.response().cookies().next().unwrap().into_owned();
+
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(90)).await;
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/whoami")
+                .cookie(cookie)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::UNAUTHORIZED,
+            "the absolute lifetime cap must force logout even with continuous activity"
+        );
+    }
 }
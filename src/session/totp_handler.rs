@@ -0,0 +1,53 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::mfa::{MfaFactor, MfaSubject};
+
+use super::session_auth::LoginSession;
+
+#[derive(Deserialize)]
+struct TotpCodeRequest {
+    code: String,
+}
+
+/// Handles `POST /mfa/totp`: verifies a submitted 6-digit code against the factor
+/// pending on the current session and completes the MFA challenge on success.
+async fn verify_totp<U, F>(
+    factor: web::Data<F>,
+    body: web::Json<TotpCodeRequest>,
+    login_session: LoginSession,
+) -> impl Responder
+where
+    U: DeserializeOwned + MfaSubject,
+    F: MfaFactor,
+{
+    let pending_factor = login_session.needs_mfa_with_id();
+    let user = login_session.user::<U>();
+
+    match (pending_factor, user) {
+        (Some(pending_id), Some(user)) if pending_id == factor.id() => {
+            if factor
+                .verify(&user.mfa_subject_id(), &body.code, &login_session)
+                .await
+            {
+                login_session.mfa_challenge_done(factor.id());
+                HttpResponse::Ok().finish()
+            } else {
+                HttpResponse::Unauthorized().finish()
+            }
+        }
+        _ => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+/// Registers `POST /mfa/totp` against the given [`MfaFactor`] implementation.
+pub fn totp_mfa_config<U, F>(factor: F) -> impl FnOnce(&mut web::ServiceConfig)
+where
+    U: DeserializeOwned + MfaSubject + 'static,
+    F: MfaFactor + 'static,
+{
+    move |cfg: &mut web::ServiceConfig| {
+        cfg.app_data(web::Data::new(factor))
+            .route("/mfa/totp", web::post().to(verify_totp::<U, F>));
+    }
+}
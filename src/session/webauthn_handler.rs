@@ -0,0 +1,131 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::de::DeserializeOwned;
+use webauthn_rs::prelude::{PublicKeyCredential, RegisterPublicKeyCredential};
+
+use crate::mfa::{
+    webauthn::{WebauthnCredentialStore, WebauthnFactor, WebauthnUserId},
+    MfaFactor, MfaSubject,
+};
+
+use super::session_auth::LoginSession;
+
+/// `POST /webauthn/register/start`: begins enrolling a new passkey for the logged-in user.
+///
+/// Requires a fully-authenticated session (no factor currently pending in
+/// `needs_mfa_with_id`): a session still waiting on e.g. TOTP must not be able to
+/// self-register a passkey and use it to skip that requirement.
+async fn register_start<U, S>(
+    factor: web::Data<WebauthnFactor<S>>,
+    login_session: LoginSession,
+) -> impl Responder
+where
+    U: DeserializeOwned + MfaSubject + WebauthnUserId,
+    S: WebauthnCredentialStore + Clone,
+{
+    let Some(user) = login_session.user::<U>() else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    if login_session.needs_mfa_with_id().is_some() {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    match factor.register_start(user.webauthn_user_id(), &user.mfa_subject_id(), &login_session) {
+        Ok(challenge) => HttpResponse::Ok().json(challenge),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// `POST /webauthn/register/finish`: verifies the registration response and stores the credential.
+///
+/// Gated the same way as [`register_start`]: registration requires a fully-authenticated
+/// session.
+async fn register_finish<U, S>(
+    factor: web::Data<WebauthnFactor<S>>,
+    body: web::Json<RegisterPublicKeyCredential>,
+    login_session: LoginSession,
+) -> impl Responder
+where
+    U: DeserializeOwned + MfaSubject,
+    S: WebauthnCredentialStore + Clone,
+{
+    let Some(user) = login_session.user::<U>() else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    if login_session.needs_mfa_with_id().is_some() {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    match factor.register_finish(&user.mfa_subject_id(), &body, &login_session) {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(_) => HttpResponse::BadRequest().finish(),
+    }
+}
+
+/// `POST /webauthn/auth/start`: generates a single-use challenge for the pending MFA user.
+///
+/// Only proceeds if WebAuthn is already the factor `needs_mfa_with_id` points at — see
+/// [`WebauthnFactor::auth_start`], which enforces the same check so it can't be bypassed
+/// by calling the factor directly.
+async fn auth_start<U, S>(
+    factor: web::Data<WebauthnFactor<S>>,
+    login_session: LoginSession,
+) -> impl Responder
+where
+    U: DeserializeOwned + MfaSubject,
+    S: WebauthnCredentialStore + Clone,
+{
+    if login_session.needs_mfa_with_id().as_deref() != Some(factor.id()) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let Some(user) = login_session.user::<U>() else {
+        return HttpResponse::Unauthorized().finish();
+    };
+
+    match factor.auth_start(&user.mfa_subject_id(), &login_session) {
+        Ok(challenge) => HttpResponse::Ok().json(challenge),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// `POST /webauthn/auth/finish`: verifies the signed assertion and completes the MFA challenge.
+async fn auth_finish<U, S>(
+    factor: web::Data<WebauthnFactor<S>>,
+    body: web::Json<PublicKeyCredential>,
+    login_session: LoginSession,
+) -> impl Responder
+where
+    U: DeserializeOwned + MfaSubject,
+    S: WebauthnCredentialStore + Clone + 'static,
+{
+    let pending_factor = login_session.needs_mfa_with_id();
+    if pending_factor.as_deref() != Some(factor.id()) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let response = serde_json::to_string(&body).unwrap_or_default();
+    if factor.verify("", &response, &login_session).await {
+        login_session.mfa_challenge_done(factor.id());
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::Unauthorized().finish()
+    }
+}
+
+/// Registers the `/webauthn/register/{start,finish}` and `/webauthn/auth/{start,finish}`
+/// routes for the given [`WebauthnFactor`].
+pub fn webauthn_mfa_config<U, S>(factor: WebauthnFactor<S>) -> impl FnOnce(&mut web::ServiceConfig)
+where
+    U: DeserializeOwned + MfaSubject + WebauthnUserId + 'static,
+    S: WebauthnCredentialStore + Clone + 'static,
+{
+    move |cfg: &mut web::ServiceConfig| {
+        cfg.app_data(web::Data::new(factor))
+            .route("/webauthn/register/start", web::post().to(register_start::<U, S>))
+            .route("/webauthn/register/finish", web::post().to(register_finish::<U, S>))
+            .route("/webauthn/auth/start", web::post().to(auth_start::<U, S>))
+            .route("/webauthn/auth/finish", web::post().to(auth_finish::<U, S>));
+    }
+}